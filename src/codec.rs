@@ -0,0 +1,257 @@
+use crate::segment::{BINCODE_CONFIG, WalOperation};
+use bytes::{Buf, BufMut, BytesMut};
+use std::io::{Error, ErrorKind};
+use tokio_util::codec::{Decoder, Encoder};
+
+/// Length prefix size in bytes, matching the on-disk framing used by `Segment`/`WalSegment`.
+const LENGTH_PREFIX_SIZE: usize = 4;
+
+/// Largest frame the codec will accept, so a client can't force an arbitrarily
+/// large buffer allocation (up to ~4GiB per the `u32` length prefix) before
+/// any other check, including AUTH, runs.
+const MAX_FRAME_SIZE: usize = 16 * 1024 * 1024;
+
+/// Magic prefix marking a frame as a binary `BATCH` message rather than a plain text command.
+const BATCH_MAGIC: &[u8] = b"BATCH";
+
+/// A command parsed from a framed client request.
+///
+/// `Invalid` carries the raw text so the caller can report the same
+/// "ERROR: Invalid command format" message the line-based parser used to produce.
+#[derive(Debug, PartialEq)]
+pub enum Command {
+    Set { key: String, value: String },
+    Get { key: String },
+    Delete { key: String },
+    Begin,
+    Commit,
+    Rollback,
+    Range { start: String, end: String, limit: usize },
+    ScanPrefix { prefix: String, limit: usize },
+    /// An all-or-nothing group of operations, applied via `Storage::apply_batch`.
+    Batch(Vec<WalOperation>),
+    Auth { token: String },
+    Compact,
+    Verify,
+    Invalid(String),
+}
+
+/// A response to be written back to the client as a single framed message.
+#[derive(Debug, PartialEq)]
+pub struct Response(pub String);
+
+/// Framing codec for the Mini-Bitcask wire protocol: each message is a
+/// 4-byte little-endian length prefix followed by UTF-8 command/response bytes.
+///
+/// Using a length prefix instead of a fixed-size read buffer removes the
+/// 1024-byte ceiling on command size and avoids mis-parsing commands that
+/// span or coalesce across TCP segments.
+pub struct MiniBitcaskCodec;
+
+impl Decoder for MiniBitcaskCodec {
+    type Item = Command;
+    type Error = Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Command>, Error> {
+        if src.len() < LENGTH_PREFIX_SIZE {
+            // Not enough bytes yet to read the length prefix; wait for more data.
+            return Ok(None);
+        }
+
+        let len = u32::from_le_bytes(src[..LENGTH_PREFIX_SIZE].try_into().unwrap()) as usize;
+
+        if len > MAX_FRAME_SIZE {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                format!("frame of {} bytes exceeds the {} byte limit", len, MAX_FRAME_SIZE),
+            ));
+        }
+
+        if src.len() < LENGTH_PREFIX_SIZE + len {
+            // The full frame hasn't arrived yet; reserve space and wait for more reads.
+            src.reserve(LENGTH_PREFIX_SIZE + len - src.len());
+            return Ok(None);
+        }
+
+        src.advance(LENGTH_PREFIX_SIZE); // Consume the length prefix
+        let frame = src.split_to(len); // Consume exactly one frame's worth of bytes
+
+        if frame.starts_with(BATCH_MAGIC) {
+            let payload = &frame[BATCH_MAGIC.len()..];
+            let (ops, _): (Vec<WalOperation>, usize) =
+                bincode::decode_from_slice(payload, BINCODE_CONFIG)
+                    .map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
+            return Ok(Some(Command::Batch(ops)));
+        }
+
+        let text = String::from_utf8_lossy(&frame).into_owned();
+        Ok(Some(parse_command(&text)))
+    }
+}
+
+impl Encoder<Response> for MiniBitcaskCodec {
+    type Error = Error;
+
+    fn encode(&mut self, response: Response, dst: &mut BytesMut) -> Result<(), Error> {
+        let bytes = response.0.as_bytes();
+        if bytes.len() > u32::MAX as usize {
+            return Err(Error::new(ErrorKind::InvalidData, "response too large to frame"));
+        }
+
+        dst.reserve(LENGTH_PREFIX_SIZE + bytes.len());
+        dst.put_u32_le(bytes.len() as u32);
+        dst.put_slice(bytes);
+        Ok(())
+    }
+}
+
+/// Parse a single request line into a `Command`, mirroring the whitespace-split
+/// grammar the server has always used.
+fn parse_command(request: &str) -> Command {
+    let parts: Vec<&str> = request.split_whitespace().collect();
+
+    match parts.as_slice() {
+        ["SET", key, value] => Command::Set {
+            key: key.to_string(),
+            value: value.to_string(),
+        },
+        ["GET", key] => Command::Get { key: key.to_string() },
+        ["DELETE", key] => Command::Delete { key: key.to_string() },
+        ["BEGIN"] => Command::Begin,
+        ["COMMIT"] => Command::Commit,
+        ["ROLLBACK"] => Command::Rollback,
+        ["RANGE", start, end, limit] => match limit.parse::<usize>() {
+            Ok(limit) => Command::Range {
+                start: start.to_string(),
+                end: end.to_string(),
+                limit,
+            },
+            Err(_) => Command::Invalid(request.to_string()),
+        },
+        ["SCAN", prefix, limit] => match limit.parse::<usize>() {
+            Ok(limit) => Command::ScanPrefix {
+                prefix: prefix.to_string(),
+                limit,
+            },
+            Err(_) => Command::Invalid(request.to_string()),
+        },
+        ["AUTH", token] => Command::Auth {
+            token: token.to_string(),
+        },
+        ["COMPACT"] => Command::Compact,
+        ["VERIFY"] => Command::Verify,
+        _ => Command::Invalid(request.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encode(text: &str) -> BytesMut {
+        let mut buf = BytesMut::new();
+        buf.put_u32_le(text.len() as u32);
+        buf.put_slice(text.as_bytes());
+        buf
+    }
+
+    #[test]
+    fn decode_waits_for_a_full_frame_before_returning_a_command() {
+        let mut codec = MiniBitcaskCodec;
+        let mut src = encode("GET key");
+
+        // Split the frame mid-payload: the codec must wait rather than
+        // mis-parse a partial read.
+        let second_half = src.split_off(6);
+        assert_eq!(codec.decode(&mut src).unwrap(), None);
+
+        src.unsplit(second_half);
+        assert_eq!(
+            codec.decode(&mut src).unwrap(),
+            Some(Command::Get { key: "key".to_string() })
+        );
+    }
+
+    #[test]
+    fn decode_waits_when_only_the_length_prefix_has_arrived() {
+        let mut codec = MiniBitcaskCodec;
+        let mut src = BytesMut::new();
+        src.put_u32_le(10); // Declares a 10-byte frame with no payload bytes yet
+        assert_eq!(codec.decode(&mut src).unwrap(), None);
+    }
+
+    #[test]
+    fn decode_rejects_a_frame_over_the_size_limit() {
+        let mut codec = MiniBitcaskCodec;
+        let mut src = BytesMut::new();
+        src.put_u32_le((MAX_FRAME_SIZE + 1) as u32);
+        assert_eq!(codec.decode(&mut src).unwrap_err().kind(), ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn decode_accepts_a_frame_exactly_at_the_size_limit() {
+        // Only the declared length is checked up front; a real `MAX_FRAME_SIZE`
+        // payload isn't constructed here, just confirm the boundary itself doesn't
+        // trip the rejection meant for frames strictly larger than the limit.
+        let mut codec = MiniBitcaskCodec;
+        let mut src = BytesMut::new();
+        src.put_u32_le(MAX_FRAME_SIZE as u32);
+        // Not enough bytes have arrived yet, but that's a different (Ok(None)) path.
+        assert_eq!(codec.decode(&mut src).unwrap(), None);
+    }
+
+    #[test]
+    fn decode_parses_a_batch_frame_via_the_magic_prefix() {
+        let mut codec = MiniBitcaskCodec;
+        let ops = vec![
+            WalOperation::Set {
+                key: "a".to_string(),
+                value: "1".to_string(),
+            },
+            WalOperation::Delete { key: "b".to_string() },
+        ];
+        let encoded_ops = bincode::encode_to_vec(&ops, BINCODE_CONFIG).unwrap();
+
+        let mut payload = BATCH_MAGIC.to_vec();
+        payload.extend_from_slice(&encoded_ops);
+
+        let mut src = BytesMut::new();
+        src.put_u32_le(payload.len() as u32);
+        src.put_slice(&payload);
+
+        assert_eq!(codec.decode(&mut src).unwrap(), Some(Command::Batch(ops)));
+    }
+
+    #[test]
+    fn decode_treats_any_frame_starting_with_batch_magic_as_binary() {
+        // The magic prefix check happens before any text parsing, so trailing
+        // bytes that aren't a valid bincode-encoded op list are a decode
+        // error, not a plain-text `Invalid` command.
+        let mut codec = MiniBitcaskCodec;
+        let mut src = encode("BATCHERY");
+        assert_eq!(codec.decode(&mut src).unwrap_err().kind(), ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn decode_parses_plain_text_commands() {
+        let mut codec = MiniBitcaskCodec;
+        let mut src = encode("SET a 1");
+        assert_eq!(
+            codec.decode(&mut src).unwrap(),
+            Some(Command::Set {
+                key: "a".to_string(),
+                value: "1".to_string(),
+            })
+        );
+    }
+
+    #[test]
+    fn decode_reports_invalid_on_unrecognized_text() {
+        let mut codec = MiniBitcaskCodec;
+        let mut src = encode("NOT A COMMAND AT ALL");
+        assert_eq!(
+            codec.decode(&mut src).unwrap(),
+            Some(Command::Invalid("NOT A COMMAND AT ALL".to_string()))
+        );
+    }
+}
@@ -1,21 +1,103 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::ops::Bound;
 
 pub struct Index {
-    pub map: HashMap<String, u64>,
+    pub map: HashMap<String, u64>, // Point lookups (GET/DELETE)
+    ordered: BTreeMap<String, u64>, // Ordered lookups (RANGE/SCAN)
 }
 
 impl Index {
     pub fn new() -> Self {
         Index {
             map: HashMap::new(),
+            ordered: BTreeMap::new(),
         }
     }
 
     pub fn insert(&mut self, key: &str, offset: u64) {
         self.map.insert(key.to_string(), offset);
+        self.ordered.insert(key.to_string(), offset);
+    }
+
+    /// Remove a key from both the point-lookup map and the ordered index.
+    pub fn remove(&mut self, key: &str) {
+        self.map.remove(key);
+        self.ordered.remove(key);
     }
 
     pub fn get_offset(&self, key: &str) -> Option<u64> {
         self.map.get(key).cloned()
     }
+
+    /// Return up to `limit` `(key, offset)` pairs in key order, from `start`
+    /// to `end` inclusive. A `None` bound is unbounded on that side.
+    ///
+    /// `BTreeMap::range` panics if `start > end`, so that case is treated as
+    /// an empty range rather than passed through.
+    pub fn range(&self, start: Option<&str>, end: Option<&str>, limit: usize) -> Vec<(String, u64)> {
+        if let (Some(start), Some(end)) = (start, end) {
+            if start > end {
+                return Vec::new();
+            }
+        }
+
+        let start_bound = match start {
+            Some(key) => Bound::Included(key.to_string()),
+            None => Bound::Unbounded,
+        };
+        let end_bound = match end {
+            Some(key) => Bound::Included(key.to_string()),
+            None => Bound::Unbounded,
+        };
+
+        self.ordered
+            .range((start_bound, end_bound))
+            .take(limit)
+            .map(|(key, offset)| (key.clone(), *offset))
+            .collect()
+    }
+
+    /// Return up to `limit` `(key, offset)` pairs whose key starts with `prefix`, in key order.
+    pub fn prefix(&self, prefix: &str, limit: usize) -> Vec<(String, u64)> {
+        self.ordered
+            .range(prefix.to_string()..)
+            .take_while(|(key, _)| key.starts_with(prefix))
+            .take(limit)
+            .map(|(key, offset)| (key.clone(), *offset))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn index_with(entries: &[(&str, u64)]) -> Index {
+        let mut index = Index::new();
+        for (key, offset) in entries {
+            index.insert(key, *offset);
+        }
+        index
+    }
+
+    #[test]
+    fn range_with_start_after_end_is_empty_not_a_panic() {
+        let index = index_with(&[("a", 0), ("m", 1), ("z", 2)]);
+        assert_eq!(index.range(Some("z"), Some("a"), 10), Vec::new());
+    }
+
+    #[test]
+    fn range_with_start_before_end_is_unaffected() {
+        let index = index_with(&[("a", 0), ("m", 1), ("z", 2)]);
+        assert_eq!(
+            index.range(Some("a"), Some("z"), 10),
+            vec![("a".to_string(), 0), ("m".to_string(), 1), ("z".to_string(), 2)]
+        );
+    }
+
+    #[test]
+    fn range_with_equal_bounds_returns_that_one_key() {
+        let index = index_with(&[("a", 0), ("m", 1), ("z", 2)]);
+        assert_eq!(index.range(Some("m"), Some("m"), 10), vec![("m".to_string(), 1)]);
+    }
 }
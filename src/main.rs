@@ -1,25 +1,31 @@
+mod codec;
 mod server;
 mod index;
 mod segment;
 mod storage;
+mod transaction;
+
+use server::{run_server, run_server_tls};
 
-use server::run_server;
-use console_subscriber;
 #[tokio::main]
 async fn main() {
-    //let path = Path::new("data.db");
-    //let mut storage = Storage::new(path).unwrap();
-    //
-    //storage.begin_transaction().unwrap();
-    //storage.set("user1", "data1").unwrap();
-    //storage.set("user2", "data2").unwrap();
-    //storage.commit().unwrap();
-    //
-    //println!("{:?}", storage.get("user1"));
-    //
-    //storage.delete("user2").unwrap();
-    //
     console_subscriber::init();
     let addr = "127.0.0.1:8080";
-    run_server(addr).await.unwrap();
+
+    // MINIBITCASK_AUTH_TOKEN, if set, requires clients to AUTH before any
+    // other command is accepted. Left unset, the server stays open, for local development.
+    let auth_token = std::env::var("MINIBITCASK_AUTH_TOKEN").ok();
+
+    // MINIBITCASK_TLS_CERT/MINIBITCASK_TLS_KEY, if both set, terminate TLS on
+    // the listening socket so the store can be exposed beyond localhost.
+    let tls_paths = std::env::var("MINIBITCASK_TLS_CERT")
+        .ok()
+        .zip(std::env::var("MINIBITCASK_TLS_KEY").ok());
+
+    match tls_paths {
+        Some((cert_path, key_path)) => run_server_tls(addr, &cert_path, &key_path, auth_token)
+            .await
+            .unwrap(),
+        None => run_server(addr, auth_token).await.unwrap(),
+    }
 }
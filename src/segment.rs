@@ -5,7 +5,50 @@ use std::fs::{File, OpenOptions};
 use std::io::{Error, ErrorKind, Read, Seek, SeekFrom, Write};
 use std::path::Path;
 
-const BINCODE_CONFIG: Configuration = config::standard(); // Standard bincode configuration
+/// Key written into a record's `key` field to mark it as tombstoned (deleted).
+pub const TOMBSTONE_KEY: &str = "deleted";
+
+pub(crate) const BINCODE_CONFIG: Configuration = config::standard(); // Standard bincode configuration
+
+/// Size in bytes of a frame's `[len: u32][crc32: u32]` header, preceding the payload.
+pub(crate) const FRAME_HEADER_SIZE: u64 = 8;
+
+/// Write one `[len][crc32][payload]` frame at the file's current position.
+fn write_frame(file: &mut File, payload: &[u8]) -> Result<(), Error> {
+    let size = payload.len() as u32;
+    let crc = crc32fast::hash(payload); // Fold the CRC over the payload bytes as they're written
+    file.write_all(&size.to_le_bytes())?;
+    file.write_all(&crc.to_le_bytes())?;
+    file.write_all(payload)?;
+    Ok(())
+}
+
+/// Read one `[len][crc32][payload]` frame from the file's current position,
+/// verifying the checksum against the payload before returning it.
+///
+/// `frame_offset` is only used to produce a useful error message on mismatch.
+fn read_frame(file: &mut File, frame_offset: u64) -> Result<Vec<u8>, Error> {
+    let mut size_buf = [0u8; 4];
+    file.read_exact(&mut size_buf)?;
+    let size = u32::from_le_bytes(size_buf) as usize;
+
+    let mut crc_buf = [0u8; 4];
+    file.read_exact(&mut crc_buf)?;
+    let expected_crc = u32::from_le_bytes(crc_buf);
+
+    let mut payload = vec![0u8; size];
+    file.read_exact(&mut payload)?; // Read the payload bytes, then fold the CRC over them
+
+    let actual_crc = crc32fast::hash(&payload);
+    if actual_crc != expected_crc {
+        return Err(Error::new(
+            ErrorKind::InvalidData,
+            format!("checksum mismatch at offset {}", frame_offset),
+        ));
+    }
+
+    Ok(payload)
+}
 
 /// Structure representing a record in the storage (key-value pair)
 #[derive(Serialize, Deserialize, Debug, PartialEq, Encode, Decode)]
@@ -41,6 +84,100 @@ impl Segment {
         Ok(Self { file, write_offset }) // Return the segment with the file and write offset
     }
 
+    /// The current write offset (i.e. the offset the next `set`/`delete` will be written at).
+    pub fn current_offset(&self) -> u64 {
+        self.write_offset
+    }
+
+    /// Scan every record in the segment from the start of the file, returning
+    /// each non-tombstoned record's key and offset. Used to rebuild the index
+    /// on startup when no hint file is available.
+    ///
+    /// A frame that ends mid-payload (a torn write left by a crash or power
+    /// loss) is treated as the end of valid data rather than a hard error,
+    /// so recovery stops at the last complete record instead of panicking.
+    pub fn scan(&mut self) -> Result<Vec<(String, u64)>, Error> {
+        self.file.seek(SeekFrom::Start(0))?;
+        let mut entries = Vec::new();
+        let mut offset = 0u64;
+
+        loop {
+            // Peek the size field first so we can tell a clean EOF from a mid-frame error.
+            let mut size_buf = [0u8; 4];
+            match self.file.read_exact(&mut size_buf) {
+                Ok(_) => {
+                    self.file.seek(SeekFrom::Current(-4))?; // Rewind so read_frame sees the size field
+                    let payload = match read_frame(&mut self.file, offset) {
+                        Ok(payload) => payload,
+                        Err(e) if e.kind() == ErrorKind::UnexpectedEof => break,
+                        Err(e) => return Err(e),
+                    };
+
+                    let (record, _): (Record, usize) =
+                        bincode::decode_from_slice(&payload, BINCODE_CONFIG)
+                            .map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
+
+                    if record.key != TOMBSTONE_KEY {
+                        entries.push((record.key, offset));
+                    }
+
+                    offset += FRAME_HEADER_SIZE + payload.len() as u64;
+                }
+                Err(e) if e.kind() == ErrorKind::UnexpectedEof => break,
+                Err(e) => return Err(e),
+            }
+        }
+
+        Ok(entries)
+    }
+
+    /// Walk the whole segment, verifying every record's checksum, and return
+    /// the offsets of any records that are corrupted. A corrupted record's
+    /// length prefix is still trusted to find the next frame, so a single
+    /// bad record doesn't stop the rest of the segment from being checked.
+    ///
+    /// A frame that ends mid-payload (a torn write left by a crash or power
+    /// loss) is treated as the end of valid data rather than a hard error.
+    pub fn verify(&mut self) -> Result<Vec<u64>, Error> {
+        self.file.seek(SeekFrom::Start(0))?;
+        let mut bad_offsets = Vec::new();
+        let mut offset = 0u64;
+
+        loop {
+            let mut size_buf = [0u8; 4];
+            match self.file.read_exact(&mut size_buf) {
+                Ok(_) => {
+                    let size = u32::from_le_bytes(size_buf) as usize;
+
+                    let mut crc_buf = [0u8; 4];
+                    match self.file.read_exact(&mut crc_buf) {
+                        Ok(_) => {}
+                        Err(e) if e.kind() == ErrorKind::UnexpectedEof => break,
+                        Err(e) => return Err(e),
+                    }
+                    let expected_crc = u32::from_le_bytes(crc_buf);
+
+                    let mut payload = vec![0u8; size];
+                    match self.file.read_exact(&mut payload) {
+                        Ok(_) => {}
+                        Err(e) if e.kind() == ErrorKind::UnexpectedEof => break,
+                        Err(e) => return Err(e),
+                    }
+
+                    if crc32fast::hash(&payload) != expected_crc {
+                        bad_offsets.push(offset);
+                    }
+
+                    offset += FRAME_HEADER_SIZE + size as u64;
+                }
+                Err(e) if e.kind() == ErrorKind::UnexpectedEof => break,
+                Err(e) => return Err(e),
+            }
+        }
+
+        Ok(bad_offsets)
+    }
+
     /// Set a key-value pair in the segment and returns the offset where it was written
     pub fn set(&mut self, key: &str, value: &str) -> Result<u64, Error> {
         let record = Record {
@@ -54,11 +191,9 @@ impl Segment {
 
         let offset = self.write_offset; // The current position in the file
 
-        let size = serialized.len() as u32;
-        // Write the size of the record followed by the record itself
-        self.file.write_all(&size.to_le_bytes())?;
-        self.file.write_all(&serialized)?;
-        self.write_offset += 4 + serialized.len() as u64; // Update the write offset
+        // Write the frame (size + crc32 + record bytes)
+        write_frame(&mut self.file, &serialized)?;
+        self.write_offset += FRAME_HEADER_SIZE + serialized.len() as u64; // Update the write offset
 
         Ok(offset)
     }
@@ -67,14 +202,9 @@ impl Segment {
     pub fn get(&mut self, offset: u64) -> Result<Option<Record>, Error> {
         self.file.seek(SeekFrom::Start(offset))?; // Move to the specified offset
 
-        let mut size_buf = [0u8; 4];
-        self.file.read_exact(&mut size_buf)?; // Read the size of the record
-        let size = u32::from_le_bytes(size_buf) as usize;
+        let payload = read_frame(&mut self.file, offset)?; // Read the frame, verifying its checksum
 
-        let mut buffer = vec![0u8; size];
-        self.file.read_exact(&mut buffer)?; // Read the serialized record
-
-        match bincode::decode_from_slice(&buffer, BINCODE_CONFIG) {
+        match bincode::decode_from_slice(&payload, BINCODE_CONFIG) {
             Ok((record, _)) => Ok(Some(record)), // Successfully decoded the record
             Err(e) => Err(Error::new(ErrorKind::InvalidData, e)), // Error while decoding
         }
@@ -86,17 +216,15 @@ impl Segment {
 
         // Create a "deleted" record with an empty value
         let deleted_record = Record {
-            key: String::from("deleted"),
+            key: String::from(TOMBSTONE_KEY),
             value: String::from(""),
         };
 
         let serialized = bincode::encode_to_vec(&deleted_record, BINCODE_CONFIG)
             .map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
 
-        let size = serialized.len() as u32;
-        // Write the size of the "deleted" record followed by the record itself
-        self.file.write_all(&size.to_le_bytes())?;
-        self.file.write_all(&serialized)?;
+        // Write the frame (size + crc32 + record bytes) for the tombstone
+        write_frame(&mut self.file, &serialized)?;
 
         Ok(())
     }
@@ -114,6 +242,7 @@ impl WalSegment {
             .create(true) // Create the file if it doesn't exist
             .read(true) // Allow reading from the file
             .write(true) // Allow writing to the file
+            .truncate(false) // Keep any leftover ops from an unclean shutdown, for replay
             .open(path)?; // Open the file at the given path
         Ok(Self { file }) // Return the WAL segment
     }
@@ -123,10 +252,8 @@ impl WalSegment {
         let serialized = bincode::encode_to_vec(op, BINCODE_CONFIG)
             .map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
 
-        let size = serialized.len() as u32;
-        // Write the size of the operation followed by the serialized operation
-        self.file.write_all(&size.to_le_bytes())?;
-        self.file.write_all(&serialized)?;
+        // Write the frame (size + crc32 + operation bytes)
+        write_frame(&mut self.file, &serialized)?;
         Ok(())
     }
 
@@ -134,17 +261,22 @@ impl WalSegment {
     pub fn read_operations(&mut self) -> Result<Vec<WalOperation>, Error> {
         self.file.seek(SeekFrom::Start(0))?; // Start reading from the beginning of the file
         let mut operations = Vec::new();
+        let mut offset = 0u64;
 
         loop {
+            // Peek the size field first so we can tell a clean EOF from a mid-frame error.
             let mut size_buf = [0u8; 4];
             match self.file.read_exact(&mut size_buf) {
                 Ok(_) => {
                     let size = u32::from_le_bytes(size_buf) as usize;
-                    let mut buffer = vec![0u8; size];
-                    self.file.read_exact(&mut buffer)?; // Read the serialized operation
-                    let (op, _) = bincode::decode_from_slice(&buffer, BINCODE_CONFIG)
+                    self.file.seek(SeekFrom::Current(-4))?; // Rewind so read_frame sees the size field
+                    let payload = read_frame(&mut self.file, offset)?; // Read the frame, verifying its checksum
+
+                    let (op, _) = bincode::decode_from_slice(&payload, BINCODE_CONFIG)
                         .map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
                     operations.push(op); // Add the operation to the list
+
+                    offset += FRAME_HEADER_SIZE + size as u64;
                 }
                 Err(e) if e.kind() == ErrorKind::UnexpectedEof => break, // Break if EOF is reached
                 Err(e) => return Err(e),                                 // Propagate other errors
@@ -161,3 +293,152 @@ impl WalSegment {
         Ok(())
     }
 }
+
+/// A single entry in a segment's hint file: where a live key lives in the
+/// compacted segment, so the index can be rebuilt without scanning every record.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Encode, Decode)]
+pub struct HintEntry {
+    pub key: String,
+    pub offset: u64,
+    pub size: u32,
+}
+
+/// Write a segment's hint file: one `HintEntry` per live key, bincode-encoded
+/// and length-prefixed the same way segment records are.
+pub fn write_hint_file(path: &Path, entries: &[HintEntry]) -> Result<(), Error> {
+    let mut file = OpenOptions::new()
+        .create(true)
+        .write(true)
+        .truncate(true)
+        .open(path)?;
+
+    for entry in entries {
+        let serialized = bincode::encode_to_vec(entry, BINCODE_CONFIG)
+            .map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
+        let size = serialized.len() as u32;
+        file.write_all(&size.to_le_bytes())?;
+        file.write_all(&serialized)?;
+    }
+
+    Ok(())
+}
+
+/// Read a segment's hint file, if one exists. Returns `None` when no hint
+/// file is present at `path`, so callers can fall back to a full segment scan.
+pub fn read_hint_file(path: &Path) -> Result<Option<Vec<HintEntry>>, Error> {
+    if !path.exists() {
+        return Ok(None);
+    }
+
+    let mut file = OpenOptions::new().read(true).open(path)?;
+    let mut entries = Vec::new();
+
+    loop {
+        let mut size_buf = [0u8; 4];
+        match file.read_exact(&mut size_buf) {
+            Ok(_) => {
+                let size = u32::from_le_bytes(size_buf) as usize;
+                let mut buffer = vec![0u8; size];
+                file.read_exact(&mut buffer)?;
+                let (entry, _) = bincode::decode_from_slice(&buffer, BINCODE_CONFIG)
+                    .map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
+                entries.push(entry);
+            }
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof => break,
+            Err(e) => return Err(e),
+        }
+    }
+
+    Ok(Some(entries))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    /// A scratch path under the OS temp dir, unique per test run so parallel
+    /// test threads don't collide on the same file.
+    fn scratch_path(name: &str) -> std::path::PathBuf {
+        static COUNTER: AtomicU64 = AtomicU64::new(0);
+        let n = COUNTER.fetch_add(1, Ordering::Relaxed);
+        std::env::temp_dir().join(format!("mini_bitcask_test_{}_{}_{}", std::process::id(), name, n))
+    }
+
+    #[test]
+    fn get_detects_a_corrupted_checksum() {
+        let path = scratch_path("checksum");
+        let mut segment = Segment::new(&path).unwrap();
+        let offset = segment.set("key", "value").unwrap();
+
+        // Flip a byte inside the payload, past the frame header, to corrupt the record
+        // without disturbing the length prefix that `read_frame` trusts to find the frame.
+        let mut file = OpenOptions::new().write(true).open(&path).unwrap();
+        file.seek(SeekFrom::Start(offset + FRAME_HEADER_SIZE)).unwrap();
+        file.write_all(&[0xFF]).unwrap();
+        drop(file);
+
+        let err = segment.get(offset).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+        assert!(err.to_string().contains("checksum mismatch"));
+
+        std::fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn verify_reports_only_the_corrupted_offset() {
+        let path = scratch_path("verify");
+        let mut segment = Segment::new(&path).unwrap();
+        segment.set("a", "1").unwrap();
+        let bad_offset = segment.set("b", "2").unwrap();
+        segment.set("c", "3").unwrap();
+
+        let mut file = OpenOptions::new().write(true).open(&path).unwrap();
+        file.seek(SeekFrom::Start(bad_offset + FRAME_HEADER_SIZE)).unwrap();
+        file.write_all(&[0xFF]).unwrap();
+        drop(file);
+
+        let bad_offsets = segment.verify().unwrap();
+        assert_eq!(bad_offsets, vec![bad_offset]);
+
+        std::fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn scan_stops_cleanly_at_a_torn_trailing_frame() {
+        let path = scratch_path("torn_tail");
+        let mut segment = Segment::new(&path).unwrap();
+        segment.set("a", "1").unwrap();
+        let torn_offset = segment.set("b", "2").unwrap();
+        drop(segment);
+
+        // Simulate a crash mid-write: truncate partway into the last record's payload.
+        let file = OpenOptions::new().write(true).open(&path).unwrap();
+        file.set_len(torn_offset + FRAME_HEADER_SIZE + 1).unwrap();
+        drop(file);
+
+        let mut segment = Segment::new(&path).unwrap();
+        let entries = segment.scan().unwrap();
+        assert_eq!(entries, vec![("a".to_string(), 0)]);
+
+        std::fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn verify_stops_cleanly_at_a_torn_trailing_frame() {
+        let path = scratch_path("torn_tail_verify");
+        let mut segment = Segment::new(&path).unwrap();
+        segment.set("a", "1").unwrap();
+        let torn_offset = segment.set("b", "2").unwrap();
+        drop(segment);
+
+        let file = OpenOptions::new().write(true).open(&path).unwrap();
+        file.set_len(torn_offset + FRAME_HEADER_SIZE + 1).unwrap();
+        drop(file);
+
+        let mut segment = Segment::new(&path).unwrap();
+        assert_eq!(segment.verify().unwrap(), Vec::<u64>::new());
+
+        std::fs::remove_file(&path).ok();
+    }
+}
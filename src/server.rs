@@ -1,14 +1,27 @@
+use crate::codec::{Command, MiniBitcaskCodec, Response};
+use crate::segment::WalOperation;
 use crate::storage::Storage;
+use crate::transaction::TransactionContext;
+use futures::{SinkExt, StreamExt};
+use rustls_pemfile::{certs, pkcs8_private_keys};
+use std::fs::File;
+use std::io::BufReader;
 use std::sync::{Arc, Mutex};
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
-use tokio::net::{TcpListener, TcpStream};
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::TcpListener;
+use tokio_rustls::TlsAcceptor;
+use tokio_rustls::rustls::{Certificate, PrivateKey, ServerConfig};
+use tokio_util::codec::Framed;
 
-/// Start the TCP server
-pub async fn run_server(addr: &str) -> Result<(), std::io::Error> {
+/// Start the TCP server in plaintext mode. When `auth_token` is `Some`, every
+/// connection must send `AUTH <token>` matching it before any other command
+/// is accepted; when `None`, the server stays open (suitable for local dev).
+pub async fn run_server(addr: &str, auth_token: Option<String>) -> Result<(), std::io::Error> {
     let listener = TcpListener::bind(addr).await?; // Create a listener on the specified address
     let storage = Arc::new(Mutex::new(
         Storage::new(std::path::Path::new("./db")).unwrap(),
     )); // Data storage
+    let auth_token = auth_token.map(Arc::new);
 
     println!("Server listening on {}", addr);
 
@@ -16,30 +29,113 @@ pub async fn run_server(addr: &str) -> Result<(), std::io::Error> {
     loop {
         let (socket, _) = listener.accept().await?; // Wait for new connections
         let storage_clone = Arc::clone(&storage);
+        let auth_token = auth_token.clone();
 
         // For each client, spawn an asynchronous task
         tokio::spawn(async move {
-            handle_client(socket, storage_clone).await;
+            handle_client(socket, storage_clone, auth_token).await;
         });
     }
 }
 
-/// Handle the client connection
-async fn handle_client(mut stream: TcpStream, storage: Arc<Mutex<Storage>>) {
-    let mut buffer = [0; 1024]; // Buffer for reading data
+/// Start the TCP server with TLS termination, using the certificate chain and
+/// private key at `cert_path`/`key_path`. Connections are accepted exactly as
+/// in `run_server`, but each socket is upgraded to TLS before `handle_client`
+/// sees it. See `run_server` for `auth_token`'s semantics.
+pub async fn run_server_tls(
+    addr: &str,
+    cert_path: &str,
+    key_path: &str,
+    auth_token: Option<String>,
+) -> Result<(), std::io::Error> {
+    let listener = TcpListener::bind(addr).await?; // Create a listener on the specified address
+    let storage = Arc::new(Mutex::new(
+        Storage::new(std::path::Path::new("./db")).unwrap(),
+    )); // Data storage
+    let auth_token = auth_token.map(Arc::new);
+
+    let tls_config = load_tls_config(cert_path, key_path)?;
+    let acceptor = TlsAcceptor::from(Arc::new(tls_config));
 
+    println!("Server listening on {} (TLS)", addr);
+
+    // Connection waiting loop
     loop {
-        match stream.read(&mut buffer).await {
-            Ok(0) => break, // If connection is closed, break the loop
-            Ok(n) => {
-                let request = String::from_utf8_lossy(&buffer[..n]); // Convert bytes to string
-                println!("Received request: {}", request);
+        let (socket, _) = listener.accept().await?; // Wait for new connections
+        let storage_clone = Arc::clone(&storage);
+        let auth_token = auth_token.clone();
+        let acceptor = acceptor.clone();
+
+        // For each client, spawn an asynchronous task
+        tokio::spawn(async move {
+            match acceptor.accept(socket).await {
+                Ok(tls_stream) => handle_client(tls_stream, storage_clone, auth_token).await,
+                Err(e) => eprintln!("TLS handshake failed: {}", e),
+            }
+        });
+    }
+}
+
+/// Load a PEM certificate chain and PKCS#8 private key from disk and build a
+/// rustls server config that presents them to clients.
+fn load_tls_config(cert_path: &str, key_path: &str) -> Result<ServerConfig, std::io::Error> {
+    let mut cert_reader = BufReader::new(File::open(cert_path)?);
+    let cert_chain = certs(&mut cert_reader)
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?
+        .into_iter()
+        .map(Certificate)
+        .collect();
+
+    let mut key_reader = BufReader::new(File::open(key_path)?);
+    let mut keys = pkcs8_private_keys(&mut key_reader)
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+    let key = PrivateKey(keys.pop().ok_or_else(|| {
+        std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            "no PKCS#8 private key found in key file",
+        )
+    })?);
+
+    ServerConfig::builder()
+        .with_safe_defaults()
+        .with_no_client_auth()
+        .with_single_cert(cert_chain, key)
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))
+}
+
+/// Handle the client connection. Generic over the stream type so the same
+/// command loop runs identically over plaintext `TcpStream`s and TLS `TlsStream`s.
+async fn handle_client<S>(stream: S, storage: Arc<Mutex<Storage>>, auth_token: Option<Arc<String>>)
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let mut framed = Framed::new(stream, MiniBitcaskCodec);
+
+    // If a shared secret is configured, the connection must authenticate
+    // with AUTH <token> before any other command is accepted.
+    if let Some(secret) = &auth_token {
+        if !authenticate(&mut framed, secret).await {
+            println!("Client disconnected (auth failed)");
+            return;
+        }
+    }
 
-                // Process the request
-                let response = process_request(&request, &storage);
+    // Each connection owns its own transaction state, isolated from other connections.
+    let mut tx = TransactionContext::new();
 
-                // Send the response back to the client
-                if let Err(e) = stream.write_all(response.as_bytes()).await {
+    while let Some(result) = framed.next().await {
+        match result {
+            Ok(command) => {
+                match &command {
+                    // Never log AUTH: a repeat attempt after authentication still
+                    // carries the real secret in the token field.
+                    Command::Auth { .. } => println!("Received command: AUTH <redacted>"),
+                    _ => println!("Received command: {:?}", command),
+                }
+
+                let response = process_request(command, &storage, &mut tx);
+
+                if let Err(e) = framed.send(Response(response)).await {
                     eprintln!("Failed to send response: {}", e);
                     break;
                 }
@@ -54,23 +150,128 @@ async fn handle_client(mut stream: TcpStream, storage: Arc<Mutex<Storage>>) {
     println!("Client disconnected");
 }
 
+/// Expect an `AUTH <token>` frame and compare it against `secret` in constant
+/// time, replying `AUTH OK`/`AUTH FAILED` accordingly. Returns whether the
+/// connection is authenticated and may proceed to the command loop.
+async fn authenticate<S>(framed: &mut Framed<S, MiniBitcaskCodec>, secret: &str) -> bool
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let authenticated = matches!(
+        framed.next().await,
+        Some(Ok(Command::Auth { token })) if constant_time_eq(token.as_bytes(), secret.as_bytes())
+    );
+
+    let response = if authenticated { "AUTH OK\n" } else { "AUTH FAILED\n" };
+    let _ = framed.send(Response(response.to_string())).await;
+
+    authenticated
+}
+
+/// Compare two byte slices in constant time (independent of where they first
+/// differ), to avoid leaking the shared secret's contents through timing.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+
+    let mut diff = 0u8;
+    for (x, y) in a.iter().zip(b.iter()) {
+        diff |= x ^ y;
+    }
+    diff == 0
+}
+
 /// Process the request based on the command
-fn process_request(request: &str, storage: &Arc<Mutex<Storage>>) -> String {
-    let parts: Vec<&str> = request.trim().split_whitespace().collect(); // Split the input by spaces
+fn process_request(command: Command, storage: &Arc<Mutex<Storage>>, tx: &mut TransactionContext) -> String {
+    match command {
+        Command::Set { key, value } => handle_set_command(&key, &value, storage, tx),
+        Command::Get { key } => handle_get_command(&key, storage),
+        Command::Delete { key } => handle_delete_command(&key, storage, tx),
+        Command::Begin => handle_begin_command(tx),
+        Command::Commit => handle_commit_command(storage, tx),
+        Command::Rollback => handle_rollback_command(tx),
+        Command::Range { start, end, limit } => handle_range_command(&start, &end, limit, storage),
+        Command::ScanPrefix { prefix, limit } => handle_scan_command(&prefix, limit, storage),
+        Command::Batch(ops) => handle_batch_command(ops, storage),
+        Command::Auth { .. } => "ERROR: Already authenticated\n".to_string(),
+        Command::Compact => handle_compact_command(storage),
+        Command::Verify => handle_verify_command(storage),
+        Command::Invalid(_) => "ERROR: Invalid command format\n".to_string(),
+    }
+}
+
+/// Handle the COMPACT command: merge the segment, reclaiming space from overwrites and deletes
+fn handle_compact_command(storage: &Arc<Mutex<Storage>>) -> String {
+    match storage.lock().unwrap().compact() {
+        Ok(_) => "COMPACT OK\n".to_string(),
+        Err(e) => format!("ERROR: {}\n", e),
+    }
+}
+
+/// Handle the VERIFY command: report the offsets of any corrupted records
+fn handle_verify_command(storage: &Arc<Mutex<Storage>>) -> String {
+    match storage.lock().unwrap().verify() {
+        Ok(bad_offsets) if bad_offsets.is_empty() => "VERIFY OK, no corruption found\n".to_string(),
+        Ok(bad_offsets) => {
+            let offsets: Vec<String> = bad_offsets.iter().map(|offset| offset.to_string()).collect();
+            format!("VERIFY FOUND CORRUPTION at offsets: {}\n", offsets.join(", "))
+        }
+        Err(e) => format!("ERROR: {}\n", e),
+    }
+}
 
-    match parts.as_slice() {
-        ["SET", key, value] => handle_set_command(key, value, storage),
-        ["GET", key] => handle_get_command(key, storage),
-        ["DELETE", key] => handle_delete_command(key, storage),
-        ["BEGIN"] => handle_transaction_command("BEGIN", storage),
-        ["COMMIT"] => handle_transaction_command("COMMIT", storage),
-        ["ROLLBACK"] => handle_transaction_command("ROLLBACK", storage),
-        _ => "ERROR: Invalid command format\n".to_string(),
+/// Handle the BATCH command: an all-or-nothing group of SET/DELETE operations
+fn handle_batch_command(ops: Vec<WalOperation>, storage: &Arc<Mutex<Storage>>) -> String {
+    let count = ops.len();
+    match storage.lock().unwrap().apply_batch(ops) {
+        Ok(_) => format!("BATCH OK, {} operations applied\n", count),
+        Err(e) => format!("ERROR: {}\n", e),
+    }
+}
+
+/// Format a list of key/value pairs as one framed line per pair, terminated by "END\n".
+fn format_scan_results(results: Vec<(String, String)>) -> String {
+    let mut response = String::new();
+    for (key, value) in results {
+        response.push_str(&format!("{}: {}\n", key, value));
+    }
+    response.push_str("END\n");
+    response
+}
+
+/// Handle the RANGE command: keys between `start` and `end` inclusive, in key order
+fn handle_range_command(start: &str, end: &str, limit: usize, storage: &Arc<Mutex<Storage>>) -> String {
+    let start = if start == "-" { None } else { Some(start) };
+    let end = if end == "-" { None } else { Some(end) };
+
+    match storage.lock().unwrap().scan(start, end, limit) {
+        Ok(results) => format_scan_results(results),
+        Err(e) => format!("ERROR: {}\n", e),
     }
 }
 
-/// Handle the SET command
-fn handle_set_command(key: &str, value: &str, storage: &Arc<Mutex<Storage>>) -> String {
+/// Handle the SCAN command: keys starting with `prefix`, in key order
+fn handle_scan_command(prefix: &str, limit: usize, storage: &Arc<Mutex<Storage>>) -> String {
+    match storage.lock().unwrap().scan_prefix(prefix, limit) {
+        Ok(results) => format_scan_results(results),
+        Err(e) => format!("ERROR: {}\n", e),
+    }
+}
+
+/// Handle the SET command. If this connection has an open transaction, buffer
+/// it instead of applying it immediately.
+fn handle_set_command(
+    key: &str,
+    value: &str,
+    storage: &Arc<Mutex<Storage>>,
+    tx: &mut TransactionContext,
+) -> String {
+    if tx.is_active() {
+        tx.buffer_set(key, value);
+        return "SET OK (buffered)\n".to_string();
+    }
+
     match storage.lock().unwrap().set(key, value) {
         Ok(offset) => format!("SET OK, offset: {}\n", offset),
         Err(e) => format!("ERROR: {}\n", e),
@@ -86,30 +287,41 @@ fn handle_get_command(key: &str, storage: &Arc<Mutex<Storage>>) -> String {
     }
 }
 
-/// Handle the DELETE command
-fn handle_delete_command(key: &str, storage: &Arc<Mutex<Storage>>) -> String {
+/// Handle the DELETE command. If this connection has an open transaction, buffer
+/// it instead of applying it immediately.
+fn handle_delete_command(key: &str, storage: &Arc<Mutex<Storage>>, tx: &mut TransactionContext) -> String {
+    if tx.is_active() {
+        tx.buffer_delete(key);
+        return "DELETE OK (buffered)\n".to_string();
+    }
+
     match storage.lock().unwrap().delete(key) {
         Ok(_) => "DELETE OK\n".to_string(),
         Err(e) => format!("ERROR: {}\n", e),
     }
 }
 
-/// Handle transaction commands (BEGIN, COMMIT, ROLLBACK)
-fn handle_transaction_command(command: &str, storage: &Arc<Mutex<Storage>>) -> String {
-    let mut storage = storage.lock().unwrap();
-    match command {
-        "BEGIN" => match storage.begin_transaction() {
-            Ok(_) => "BEGIN TRANSACTION OK\n".to_string(),
-            Err(_) => "ERROR\n".to_string(),
-        },
-        "COMMIT" => match storage.commit() {
+/// Handle the BEGIN command: start buffering this connection's operations
+fn handle_begin_command(tx: &mut TransactionContext) -> String {
+    match tx.begin() {
+        Ok(_) => "BEGIN TRANSACTION OK\n".to_string(),
+        Err(_) => "ERROR\n".to_string(),
+    }
+}
+
+/// Handle the COMMIT command: apply this connection's buffered operations atomically
+fn handle_commit_command(storage: &Arc<Mutex<Storage>>, tx: &mut TransactionContext) -> String {
+    match tx.take_for_commit() {
+        Ok(ops) => match storage.lock().unwrap().apply_batch(ops) {
             Ok(_) => "COMMIT OK\n".to_string(),
             Err(_) => "ERROR\n".to_string(),
         },
-        "ROLLBACK" => match storage.rollback() {
-            Ok(_) => "ROLLBACK OK\n".to_string(),
-            Err(_) => "ERROR\n".to_string(),
-        },
-        _ => "ERROR: Invalid transaction command\n".to_string(),
+        Err(_) => "ERROR\n".to_string(),
     }
 }
+
+/// Handle the ROLLBACK command: discard this connection's buffered operations
+fn handle_rollback_command(tx: &mut TransactionContext) -> String {
+    tx.rollback();
+    "ROLLBACK OK\n".to_string()
+}
@@ -1,27 +1,47 @@
 use crate::index::Index;
-use crate::segment::{Segment, WalOperation, WalSegment};
-use std::io::{Error, ErrorKind};
-use std::path::Path;
+use crate::segment::{
+    FRAME_HEADER_SIZE, HintEntry, Segment, WalOperation, WalSegment, read_hint_file, write_hint_file,
+};
+use std::fs;
+use std::io::Error;
+use std::path::{Path, PathBuf};
 
 pub struct Storage {
+    path: PathBuf,
     segment: Segment,
     index: Index,
     wal: WalSegment,
-    in_transaction: bool, // Flag to track if a transaction is in progress
 }
 
 impl Storage {
     // Constructor to create a new Storage instance
     pub fn new(path: &Path) -> Result<Self, Error> {
-        let segment = Segment::new(path)?; // Create a new Segment from the given path
-        let index = Index::new(); // Create a new Index instance
+        let mut segment = Segment::new(path)?; // Create a new Segment from the given path
+        let mut index = Index::new(); // Create a new Index instance
+
+        // Rebuild the index from the segment's hint file if one is present
+        // (written by the last compaction); otherwise fall back to a full scan.
+        let hint_path = path.with_extension("hint");
+        match read_hint_file(&hint_path)? {
+            Some(entries) => {
+                for entry in entries {
+                    index.insert(&entry.key, entry.offset);
+                }
+            }
+            None => {
+                for (key, offset) in segment.scan()? {
+                    index.insert(&key, offset);
+                }
+            }
+        }
+
         let wal_path = path.with_extension("wal"); // Set the WAL file path
         let wal = WalSegment::new(&wal_path)?; // Create a new WAL segment
         let mut storage = Self {
+            path: path.to_path_buf(),
             segment,
             index,
             wal,
-            in_transaction: false, // Initially, there is no transaction
         };
 
         // Read operations from the WAL (Write-Ahead Log) and apply them to the storage
@@ -36,7 +56,7 @@ impl Storage {
                     WalOperation::Delete { key } => {
                         if let Some(offset) = storage.index.get_offset(&key) {
                             storage.segment.delete(offset)?; // Perform the DELETE operation
-                            storage.index.map.remove(&key); // Remove the key from the index
+                            storage.index.remove(&key); // Remove the key from the index
                         }
                     }
                 }
@@ -47,26 +67,82 @@ impl Storage {
         Ok(storage)
     }
 
-    // Start a new transaction
-    pub fn begin_transaction(&mut self) -> Result<(), Error> {
-        if self.in_transaction {
-            return Err(Error::new(
-                ErrorKind::Other,
-                "Transaction already in progress", // Prevent starting a new transaction if one is already active
-            ));
+    /// Merge/compact the segment: copy every live entry (per the in-memory
+    /// index) into a fresh segment file, drop tombstoned and overwritten
+    /// records, then atomically swap the new file in.
+    ///
+    /// The old segment stays authoritative until the final `fs::rename`
+    /// succeeds, so an interruption mid-compaction leaves the store intact.
+    pub fn compact(&mut self) -> Result<(), Error> {
+        let compact_path = self.path.with_extension("compact");
+        let mut new_segment = Segment::new(&compact_path)?;
+        let mut new_index = Index::new();
+        let mut hint_entries = Vec::new();
+
+        // Collect up front so we're not borrowing `self.index` while writing to `self.segment`.
+        let live_entries: Vec<(String, u64)> = self
+            .index
+            .map
+            .iter()
+            .map(|(key, offset)| (key.clone(), *offset))
+            .collect();
+
+        for (key, offset) in live_entries {
+            if let Some(record) = self.segment.get(offset)? {
+                let offset_before = new_segment.current_offset();
+                let new_offset = new_segment.set(&record.key, &record.value)?;
+                let size = (new_segment.current_offset() - offset_before - FRAME_HEADER_SIZE) as u32;
+
+                new_index.insert(&key, new_offset);
+                hint_entries.push(HintEntry {
+                    key,
+                    offset: new_offset,
+                    size,
+                });
+            }
         }
-        self.in_transaction = true; // Mark the transaction as active
-        self.wal.clear()?; // Clear the WAL to start fresh
+
+        // Drop the new segment's file handle before renaming over the original.
+        drop(new_segment);
+
+        fs::rename(&compact_path, &self.path)?;
+        write_hint_file(&self.path.with_extension("hint"), &hint_entries)?;
+
+        self.segment = Segment::new(&self.path)?; // Reopen the now-compacted segment
+        self.index = new_index;
+
         Ok(())
     }
 
-    // Commit the current transaction
-    pub fn commit(&mut self) -> Result<(), Error> {
-        if !self.in_transaction {
-            return Err(Error::new(ErrorKind::Other, "No active transaction")); // Ensure a transaction is active
+    /// Walk the whole segment and report the offsets of any records whose
+    /// stored CRC32 doesn't match their payload, so operators can decide
+    /// whether to compact around the corruption.
+    pub fn verify(&mut self) -> Result<Vec<u64>, Error> {
+        self.segment.verify()
+    }
+
+    /// Apply a group of operations all-or-nothing: the whole batch is logged
+    /// to the WAL before anything is applied, so a crash mid-apply is
+    /// recoverable on the next `Storage::new` replay. The WAL is only
+    /// cleared once every operation has succeeded; if one errors, the batch
+    /// is aborted and left in the WAL for replay. Transactions (BEGIN/SET/
+    /// DELETE/COMMIT/ROLLBACK) are just a per-connection `TransactionContext`
+    /// buffering operations and committing them through this method.
+    pub fn apply_batch(&mut self, ops: Vec<WalOperation>) -> Result<(), Error> {
+        // A non-empty WAL means a previous batch aborted partway through and was
+        // left for recovery; refuse to start a new one on top of it rather than
+        // risk clearing it out from under the un-replayed operations.
+        if !self.wal.read_operations()?.is_empty() {
+            return Err(Error::other(
+                "WAL holds an unreplayed batch from a previous failure; restart to recover it before applying new operations",
+            ));
+        }
+
+        for op in &ops {
+            self.wal.log_operation(op)?; // Serialize the whole batch into the WAL first
         }
-        let operations = self.wal.read_operations()?; // Read the operations from the WAL
-        for op in operations {
+
+        for op in ops {
             match op {
                 WalOperation::Set { key, value } => {
                     let offset = self.segment.set(&key, &value)?; // Apply SET operation
@@ -75,37 +151,21 @@ impl Storage {
                 WalOperation::Delete { key } => {
                     if let Some(offset) = self.index.get_offset(&key) {
                         self.segment.delete(offset)?; // Apply DELETE operation
-                        self.index.map.remove(&key); // Remove the key from the index
+                        self.index.remove(&key); // Remove the key from the index
                     }
                 }
             }
         }
-        self.wal.clear()?; // Clear the WAL after committing operations
-        self.in_transaction = false; // Mark the transaction as completed
-        Ok(())
-    }
 
-    // Rollback the current transaction, clearing any uncommitted changes
-    pub fn rollback(&mut self) -> Result<(), Error> {
-        self.wal.clear()?; // Clear the WAL to discard the operations
-        self.in_transaction = false; // Mark the transaction as rolled back
+        self.wal.clear()?; // Only clear the WAL once every operation has been applied
         Ok(())
     }
 
     // Set a key-value pair in the storage
     pub fn set(&mut self, key: &str, value: &str) -> Result<u64, Error> {
-        if self.in_transaction {
-            // If in transaction, log the SET operation in the WAL instead of applying it immediately
-            self.wal.log_operation(&WalOperation::Set {
-                key: key.to_string(),
-                value: value.to_string(),
-            })?;
-            Ok(0) // Return a temporary offset
-        } else {
-            let offset = self.segment.set(key, value)?; // Apply the SET operation directly
-            self.index.insert(key, offset); // Update the index with the new offset
-            Ok(offset)
-        }
+        let offset = self.segment.set(key, value)?; // Apply the SET operation directly
+        self.index.insert(key, offset); // Update the index with the new offset
+        Ok(offset)
     }
 
     // Get the value associated with a key from the storage
@@ -120,18 +180,168 @@ impl Storage {
 
     // Delete a key from the storage
     pub fn delete(&mut self, key: &str) -> Result<(), Error> {
-        if self.in_transaction {
-            // If in transaction, log the DELETE operation in the WAL
-            self.wal.log_operation(&WalOperation::Delete {
-                key: key.to_string(),
-            })?;
-            Ok(())
-        } else {
-            if let Some(offset) = self.index.get_offset(key) {
-                self.segment.delete(offset)?; // Apply the DELETE operation
-                self.index.map.remove(key); // Remove the key from the index
+        if let Some(offset) = self.index.get_offset(key) {
+            self.segment.delete(offset)?; // Apply the DELETE operation
+            self.index.remove(key); // Remove the key from the index
+        }
+        Ok(())
+    }
+
+    /// Return up to `limit` `(key, value)` pairs with keys between `start`
+    /// and `end` inclusive, in key order. A `None` bound is unbounded on that side.
+    pub fn scan(
+        &mut self,
+        start: Option<&str>,
+        end: Option<&str>,
+        limit: usize,
+    ) -> Result<Vec<(String, String)>, Error> {
+        let mut results = Vec::new();
+        for (key, offset) in self.index.range(start, end, limit) {
+            if let Some(record) = self.segment.get(offset)? {
+                results.push((key, record.value));
+            }
+        }
+        Ok(results)
+    }
+
+    /// Return up to `limit` `(key, value)` pairs whose key starts with `prefix`, in key order.
+    pub fn scan_prefix(&mut self, prefix: &str, limit: usize) -> Result<Vec<(String, String)>, Error> {
+        let mut results = Vec::new();
+        for (key, offset) in self.index.prefix(prefix, limit) {
+            if let Some(record) = self.segment.get(offset)? {
+                results.push((key, record.value));
             }
-            Ok(())
         }
+        Ok(results)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    /// A scratch segment path under the OS temp dir, unique per test run so
+    /// parallel test threads don't collide on the same files.
+    fn scratch_path(name: &str) -> PathBuf {
+        static COUNTER: AtomicU64 = AtomicU64::new(0);
+        let n = COUNTER.fetch_add(1, Ordering::Relaxed);
+        std::env::temp_dir().join(format!("mini_bitcask_test_{}_{}_{}", std::process::id(), name, n))
+    }
+
+    /// Remove a storage path's segment, WAL, hint and compact files, ignoring
+    /// any that don't exist.
+    fn cleanup(path: &Path) {
+        for ext in ["", "wal", "hint", "compact"] {
+            let p = if ext.is_empty() {
+                path.to_path_buf()
+            } else {
+                path.with_extension(ext)
+            };
+            fs::remove_file(p).ok();
+        }
+    }
+
+    #[test]
+    fn compact_keeps_live_entries_and_drops_overwritten_and_deleted_ones() {
+        let path = scratch_path("compact");
+        let mut storage = Storage::new(&path).unwrap();
+
+        storage.set("a", "1").unwrap();
+        storage.set("b", "2").unwrap();
+        storage.set("b", "2-updated").unwrap(); // overwritten: only the new value should survive compaction
+        storage.set("c", "3").unwrap();
+        storage.delete("c").unwrap(); // tombstoned: should not survive compaction
+
+        storage.compact().unwrap();
+
+        assert_eq!(storage.get("a").unwrap(), Some("1".to_string()));
+        assert_eq!(storage.get("b").unwrap(), Some("2-updated".to_string()));
+        assert_eq!(storage.get("c").unwrap(), None);
+        assert_eq!(storage.verify().unwrap(), Vec::<u64>::new());
+
+        cleanup(&path);
+    }
+
+    #[test]
+    fn compact_result_survives_a_fresh_reopen_via_its_hint_file() {
+        let path = scratch_path("compact_reopen");
+        let mut storage = Storage::new(&path).unwrap();
+
+        storage.set("a", "1").unwrap();
+        storage.set("b", "2").unwrap();
+        storage.delete("b").unwrap();
+        storage.compact().unwrap();
+        drop(storage);
+
+        // Reopening must rebuild the index from the hint file `compact` wrote,
+        // not a full rescan, and still see exactly the post-compaction state.
+        let mut reopened = Storage::new(&path).unwrap();
+        assert_eq!(reopened.get("a").unwrap(), Some("1".to_string()));
+        assert_eq!(reopened.get("b").unwrap(), None);
+
+        cleanup(&path);
+    }
+
+    #[test]
+    fn apply_batch_applies_every_operation_or_none() {
+        let path = scratch_path("batch_atomic");
+        let mut storage = Storage::new(&path).unwrap();
+
+        storage
+            .apply_batch(vec![
+                WalOperation::Set {
+                    key: "x".to_string(),
+                    value: "1".to_string(),
+                },
+                WalOperation::Set {
+                    key: "y".to_string(),
+                    value: "2".to_string(),
+                },
+            ])
+            .unwrap();
+
+        assert_eq!(storage.get("x").unwrap(), Some("1".to_string()));
+        assert_eq!(storage.get("y").unwrap(), Some("2".to_string()));
+        // A fully-applied batch leaves nothing behind for replay.
+        assert!(storage.wal.read_operations().unwrap().is_empty());
+
+        cleanup(&path);
+    }
+
+    #[test]
+    fn apply_batch_refuses_to_start_while_the_wal_holds_an_unreplayed_batch() {
+        let path = scratch_path("batch_wal_guard");
+        let mut storage = Storage::new(&path).unwrap();
+
+        // Simulate a previous batch that crashed after logging to the WAL but
+        // before clearing it: the operation is still there, unapplied.
+        storage
+            .wal
+            .log_operation(&WalOperation::Set {
+                key: "stuck".to_string(),
+                value: "orphaned".to_string(),
+            })
+            .unwrap();
+
+        let result = storage.apply_batch(vec![WalOperation::Set {
+            key: "new".to_string(),
+            value: "op".to_string(),
+        }]);
+
+        assert!(result.is_err());
+        // The new batch must not have clobbered the WAL: the stuck operation
+        // is still there for recovery, and the new operation was never applied.
+        let remaining = storage.wal.read_operations().unwrap();
+        assert_eq!(
+            remaining,
+            vec![WalOperation::Set {
+                key: "stuck".to_string(),
+                value: "orphaned".to_string(),
+            }]
+        );
+        assert_eq!(storage.get("new").unwrap(), None);
+
+        cleanup(&path);
     }
 }
@@ -0,0 +1,166 @@
+use crate::segment::WalOperation;
+use std::io::Error;
+
+/// Per-connection transaction state.
+///
+/// Each client connection owns its own `TransactionContext`, so two clients
+/// issuing BEGIN concurrently buffer their pending operations independently
+/// instead of sharing one global flag and WAL. COMMIT hands the buffered
+/// operations to `Storage::apply_batch`, which takes the storage mutex once
+/// and applies them atomically through the durable WAL.
+pub struct TransactionContext {
+    ops: Vec<WalOperation>,
+    active: bool,
+}
+
+impl TransactionContext {
+    pub fn new() -> Self {
+        TransactionContext {
+            ops: Vec::new(),
+            active: false,
+        }
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.active
+    }
+
+    /// Start a new transaction on this connection
+    pub fn begin(&mut self) -> Result<(), Error> {
+        if self.active {
+            return Err(Error::other(
+                "Transaction already in progress", // Prevent starting a new transaction if one is already active
+            ));
+        }
+        self.active = true;
+        self.ops.clear();
+        Ok(())
+    }
+
+    /// Buffer a SET operation for this connection's in-progress transaction
+    pub fn buffer_set(&mut self, key: &str, value: &str) {
+        self.ops.push(WalOperation::Set {
+            key: key.to_string(),
+            value: value.to_string(),
+        });
+    }
+
+    /// Buffer a DELETE operation for this connection's in-progress transaction
+    pub fn buffer_delete(&mut self, key: &str) {
+        self.ops.push(WalOperation::Delete {
+            key: key.to_string(),
+        });
+    }
+
+    /// End the transaction and return its buffered operations, ready to be
+    /// applied atomically via `Storage::apply_batch`.
+    pub fn take_for_commit(&mut self) -> Result<Vec<WalOperation>, Error> {
+        if !self.active {
+            return Err(Error::other("No active transaction")); // Ensure a transaction is active
+        }
+        self.active = false;
+        Ok(std::mem::take(&mut self.ops))
+    }
+
+    /// Discard the buffered operations without applying them
+    pub fn rollback(&mut self) {
+        self.ops.clear();
+        self.active = false;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn begin_buffer_commit_returns_the_buffered_ops_in_order() {
+        let mut tx = TransactionContext::new();
+        tx.begin().unwrap();
+        tx.buffer_set("a", "1");
+        tx.buffer_delete("b");
+
+        let ops = tx.take_for_commit().unwrap();
+        assert_eq!(
+            ops,
+            vec![
+                WalOperation::Set {
+                    key: "a".to_string(),
+                    value: "1".to_string(),
+                },
+                WalOperation::Delete { key: "b".to_string() },
+            ]
+        );
+        assert!(!tx.is_active());
+    }
+
+    #[test]
+    fn begin_twice_without_committing_errors() {
+        let mut tx = TransactionContext::new();
+        tx.begin().unwrap();
+        assert!(tx.begin().is_err());
+    }
+
+    #[test]
+    fn commit_without_an_active_transaction_errors() {
+        let mut tx = TransactionContext::new();
+        assert!(tx.take_for_commit().is_err());
+    }
+
+    #[test]
+    fn rollback_discards_buffered_ops_and_clears_active() {
+        let mut tx = TransactionContext::new();
+        tx.begin().unwrap();
+        tx.buffer_set("a", "1");
+
+        tx.rollback();
+
+        assert!(!tx.is_active());
+        assert!(tx.take_for_commit().is_err()); // Nothing left to commit after a rollback
+    }
+
+    #[test]
+    fn begin_after_commit_starts_with_an_empty_buffer() {
+        let mut tx = TransactionContext::new();
+        tx.begin().unwrap();
+        tx.buffer_set("a", "1");
+        tx.take_for_commit().unwrap();
+
+        tx.begin().unwrap();
+        let ops = tx.take_for_commit().unwrap();
+        assert!(ops.is_empty());
+    }
+
+    #[test]
+    fn two_connections_transaction_state_is_fully_independent() {
+        // Each connection owns its own TransactionContext, so one connection's
+        // in-progress transaction must not be visible to, or interfere with, another's.
+        let mut conn_a = TransactionContext::new();
+        let mut conn_b = TransactionContext::new();
+
+        conn_a.begin().unwrap();
+        conn_a.buffer_set("a_key", "a_value");
+
+        assert!(!conn_b.is_active());
+        conn_b.begin().unwrap();
+        conn_b.buffer_set("b_key", "b_value");
+
+        let a_ops = conn_a.take_for_commit().unwrap();
+        let b_ops = conn_b.take_for_commit().unwrap();
+
+        assert_eq!(
+            a_ops,
+            vec![WalOperation::Set {
+                key: "a_key".to_string(),
+                value: "a_value".to_string(),
+            }]
+        );
+        assert_eq!(
+            b_ops,
+            vec![WalOperation::Set {
+                key: "b_key".to_string(),
+                value: "b_value".to_string(),
+            }]
+        );
+    }
+}